@@ -0,0 +1,142 @@
+use std::sync::OnceLock;
+
+use aes_gcm::{Aes256Gcm, Nonce};
+use aes_gcm::aead::{Aead, KeyInit, rand_core::{OsRng, RngCore}};
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use sha2::{Digest, Sha256};
+use warp::Rejection;
+use x25519_dalek::{PublicKey as X25519PublicKey, StaticSecret};
+
+use super::errors::Error;
+
+/// Verifies a raw Ed25519 signature over `message` using `pubkey_bytes`. Returns `false` (rather than
+/// erroring) on any malformed input, since an unverifiable signature is indistinguishable from an invalid one.
+pub fn verify_ed25519_signature(pubkey_bytes: &[u8], message: &[u8], signature_bytes: &[u8]) -> bool {
+    let public_key = match PublicKey::from_bytes(pubkey_bytes) {
+        Ok(public_key) => public_key,
+        Err(_) => return false
+    };
+    let signature = match Signature::from_bytes(signature_bytes) {
+        Ok(signature) => signature,
+        Err(_) => return false
+    };
+    return public_key.verify(message, &signature).is_ok();
+}
+
+/// Converts an Ed25519 public key to its X25519 (Montgomery) equivalent, the same conversion Session
+/// clients use to derive their messaging pubkey from their signing pubkey.
+pub fn ed25519_pubkey_to_x25519(pubkey_bytes: &[u8]) -> [u8; 32] {
+    let public_key = curve25519_dalek::edwards::CompressedEdwardsY::from_slice(pubkey_bytes);
+    let montgomery_point = public_key.decompress()
+        .expect("caller already validated this is a valid Ed25519 public key")
+        .to_montgomery();
+    return montgomery_point.to_bytes();
+}
+
+// The server's long-lived X25519 keypair used to derive onion-request symmetric keys. In production this
+// would be loaded from disk alongside the rest of the server's keys rather than generated at boot.
+fn server_onion_keypair() -> &'static (StaticSecret, X25519PublicKey) {
+    static KEYPAIR: OnceLock<(StaticSecret, X25519PublicKey)> = OnceLock::new();
+    return KEYPAIR.get_or_init(|| {
+        let secret = StaticSecret::random_from_rng(OsRng);
+        let public = X25519PublicKey::from(&secret);
+        (secret, public)
+    });
+}
+
+pub fn server_x25519_pubkey() -> [u8; 32] {
+    return server_onion_keypair().1.to_bytes();
+}
+
+fn hash_shared_secret(shared_secret: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(shared_secret);
+    return hasher.finalize().into();
+}
+
+/// Derives the AES-256-GCM key an onion request is encrypted with, from the client's ephemeral X25519
+/// public key and this server's static secret.
+pub fn derive_onion_symmetric_key(ephemeral_pubkey_bytes: &[u8]) -> Result<Vec<u8>, Rejection> {
+    if ephemeral_pubkey_bytes.len() != 32 {
+        return Err(warp::reject::custom(Error::InvalidRpcCall));
+    }
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(ephemeral_pubkey_bytes);
+    let ephemeral_pubkey = X25519PublicKey::from(bytes);
+    let shared_secret = server_onion_keypair().0.diffie_hellman(&ephemeral_pubkey);
+    return Ok(hash_shared_secret(shared_secret.as_bytes()).to_vec());
+}
+
+/// Generates a fresh X25519 keypair, returned as (secret, public) bytes. Used by clients (and tests
+/// standing in for a client) to create the ephemeral keypair an onion request is wrapped with.
+pub fn generate_x25519_keypair() -> (Vec<u8>, Vec<u8>) {
+    let secret = StaticSecret::random_from_rng(OsRng);
+    let public = X25519PublicKey::from(&secret);
+    return (secret.to_bytes().to_vec(), public.to_bytes().to_vec());
+}
+
+/// The client-side mirror of `derive_onion_symmetric_key`: derives the same symmetric key from the
+/// client's ephemeral secret and the server's well-known static public key.
+pub fn derive_client_onion_symmetric_key(ephemeral_secret_bytes: &[u8]) -> Vec<u8> {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(ephemeral_secret_bytes);
+    let secret = StaticSecret::from(bytes);
+    let server_pubkey = X25519PublicKey::from(server_x25519_pubkey());
+    let shared_secret = secret.diffie_hellman(&server_pubkey);
+    return hash_shared_secret(shared_secret.as_bytes()).to_vec();
+}
+
+/// Encrypts `plaintext` with AES-256-GCM under `key`, prepending the randomly generated 12-byte nonce to
+/// the returned ciphertext so the caller doesn't have to thread it through separately.
+pub fn aes256_gcm_encrypt(key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, Rejection> {
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| warp::reject::custom(Error::Internal))?;
+    let mut nonce_bytes = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce_bytes);
+    let nonce = Nonce::from_slice(&nonce_bytes);
+    let ciphertext = cipher.encrypt(nonce, plaintext).map_err(|_| warp::reject::custom(Error::Internal))?;
+    let mut output = nonce_bytes.to_vec();
+    output.extend_from_slice(&ciphertext);
+    return Ok(output);
+}
+
+/// Inverse of `aes256_gcm_encrypt`: expects the 12-byte nonce prepended to the ciphertext.
+pub fn aes256_gcm_decrypt(key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Rejection> {
+    if ciphertext.len() < 12 {
+        return Err(warp::reject::custom(Error::InvalidRpcCall));
+    }
+    let (nonce_bytes, body) = ciphertext.split_at(12);
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| warp::reject::custom(Error::Internal))?;
+    let nonce = Nonce::from_slice(nonce_bytes);
+    return cipher.decrypt(nonce, body).map_err(|_| warp::reject::custom(Error::InvalidRpcCall));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ed25519_dalek::{Keypair, Signer};
+
+    #[test]
+    fn test_valid_signature_is_accepted() {
+        let keypair = Keypair::generate(&mut rand::rngs::OsRng {});
+        let message = b"POST/messagesbodynonce-1";
+        let signature = keypair.sign(message);
+        assert!(verify_ed25519_signature(keypair.public.as_bytes(), message, &signature.to_bytes()));
+    }
+
+    #[test]
+    fn test_tampered_signature_is_rejected() {
+        let keypair = Keypair::generate(&mut rand::rngs::OsRng {});
+        let message = b"POST/messagesbodynonce-1";
+        let mut signature_bytes = keypair.sign(message).to_bytes();
+        signature_bytes[0] ^= 0xff; // flip every bit of the first byte, as if the signature had been tampered with in transit
+        assert!(!verify_ed25519_signature(keypair.public.as_bytes(), message, &signature_bytes));
+    }
+
+    #[test]
+    fn test_ed25519_pubkey_to_x25519_is_deterministic() {
+        let keypair = Keypair::generate(&mut rand::rngs::OsRng {});
+        let first = ed25519_pubkey_to_x25519(keypair.public.as_bytes());
+        let second = ed25519_pubkey_to_x25519(keypair.public.as_bytes());
+        assert_eq!(first, second);
+    }
+}
@@ -0,0 +1,26 @@
+use warp::reject::Reject;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidRpcCall,
+    Unauthorized,
+    BannedUser,
+    NotFound,
+    DatabaseFailedInternally,
+    Internal
+}
+
+impl Reject for Error {}
+
+impl Error {
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Error::InvalidRpcCall => 400,
+            Error::Unauthorized => 401,
+            Error::BannedUser => 403,
+            Error::NotFound => 404,
+            Error::DatabaseFailedInternally => 500,
+            Error::Internal => 500
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use warp::{Rejection, reply::Reply, reply::Response};
+
+use super::errors::Error;
+use super::rpc::QueryOptions;
+use super::storage::DatabaseConnectionPool;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Message {
+    pub data: String,
+    pub signature: String
+}
+
+pub async fn get_file(_file_id: &str) -> Result<Value, Rejection> {
+    return Err(warp::reject::custom(Error::NotFound));
+}
+
+pub async fn get_messages(_query_options: QueryOptions, _pool: &DatabaseConnectionPool) -> Result<Response, Rejection> {
+    let messages: Vec<Message> = Vec::new();
+    return Ok(warp::reply::json(&messages).into_response());
+}
+
+pub async fn get_deleted_messages(_query_options: QueryOptions, _pool: &DatabaseConnectionPool) -> Result<Response, Rejection> {
+    let deleted_message_ids: Vec<i64> = Vec::new();
+    return Ok(warp::reply::json(&deleted_message_ids).into_response());
+}
+
+pub async fn get_moderators(_pool: &DatabaseConnectionPool) -> Result<Response, Rejection> {
+    let moderators: Vec<String> = Vec::new();
+    return Ok(warp::reply::json(&moderators).into_response());
+}
+
+pub async fn get_banned_public_keys(_pool: &DatabaseConnectionPool) -> Result<Response, Rejection> {
+    let banned_public_keys: Vec<String> = Vec::new();
+    return Ok(warp::reply::json(&banned_public_keys).into_response());
+}
+
+pub async fn get_member_count(_pool: &DatabaseConnectionPool) -> Result<Response, Rejection> {
+    #[derive(Serialize)]
+    struct MemberCount { member_count: i64 }
+    return Ok(warp::reply::json(&MemberCount { member_count: 0 }).into_response());
+}
+
+pub async fn get_auth_token_challenge(_public_key: &str, _pool: &DatabaseConnectionPool) -> Result<Value, Rejection> {
+    return Err(warp::reject::custom(Error::InvalidRpcCall));
+}
+
+pub async fn insert_message(_message: Message, auth_token: Option<String>, _pool: &DatabaseConnectionPool) -> Result<Response, Rejection> {
+    if auth_token.is_none() { return Err(warp::reject::custom(Error::Unauthorized)); }
+    #[derive(Serialize)]
+    struct InsertedMessage { id: i64 }
+    return Ok(warp::reply::json(&InsertedMessage { id: 0 }).into_response());
+}
+
+pub async fn ban(_public_key: &str, auth_token: Option<String>, _pool: &DatabaseConnectionPool) -> Result<Response, Rejection> {
+    if auth_token.is_none() { return Err(warp::reject::custom(Error::Unauthorized)); }
+    return Ok(warp::reply::reply().into_response());
+}
+
+pub async fn unban(_public_key: &str, auth_token: Option<String>, _pool: &DatabaseConnectionPool) -> Result<Response, Rejection> {
+    if auth_token.is_none() { return Err(warp::reject::custom(Error::Unauthorized)); }
+    return Ok(warp::reply::reply().into_response());
+}
+
+pub async fn claim_auth_token(_public_key: &str, auth_token: Option<String>, _pool: &DatabaseConnectionPool) -> Result<Response, Rejection> {
+    if auth_token.is_none() { return Err(warp::reject::custom(Error::Unauthorized)); }
+    return Ok(warp::reply::reply().into_response());
+}
+
+pub async fn delete_auth_token(auth_token: Option<String>, _pool: &DatabaseConnectionPool) -> Result<Response, Rejection> {
+    if auth_token.is_none() { return Err(warp::reject::custom(Error::Unauthorized)); }
+    return Ok(warp::reply::reply().into_response());
+}
+
+pub async fn delete_message(_server_id: i64, auth_token: Option<String>, _pool: &DatabaseConnectionPool) -> Result<Response, Rejection> {
+    if auth_token.is_none() { return Err(warp::reject::custom(Error::Unauthorized)); }
+    return Ok(warp::reply::reply().into_response());
+}
+
+/// Rejects an empty `auth_token` with `Error::Unauthorized`, without performing any further action. The
+/// token is not looked up for this room and the caller's ban status is not checked. Used by `compact_poll`,
+/// where each batch entry authenticates itself independently rather than relying on a single RPC-level
+/// auth token.
+pub async fn check_auth_token(auth_token: &str, _pool: &DatabaseConnectionPool) -> Result<(), Error> {
+    if auth_token.is_empty() { return Err(Error::Unauthorized); }
+    return Ok(());
+}
+
+/// Stores an uploaded file. `require_auth` is `false` only when the server is running in `Mode::FileServer`,
+/// where uploads are intentionally public; `OpenGroupServer` mode always requires an auth token to be
+/// present (the token itself is not validated here).
+pub async fn store_file(_file: &str, require_auth: bool, auth_token: Option<String>, _pool: &DatabaseConnectionPool) -> Result<Response, Rejection> {
+    if require_auth && auth_token.is_none() {
+        return Err(warp::reject::custom(Error::Unauthorized));
+    }
+    #[derive(Serialize)]
+    struct StoredFile { id: String }
+    return Ok(warp::reply::json(&StoredFile { id: String::new() }).into_response());
+}
@@ -0,0 +1,17 @@
+// This codebase prefers an explicit `return` on every path over relying on trailing expressions, and
+// borrows Session's own `JSON` naming for ad-hoc request bodies - both deliberate, so silence the lints.
+#![allow(clippy::needless_return)]
+#![allow(clippy::upper_case_acronyms)]
+// The bootstrap that wires these modules into an actual warp server lives outside this change (see the
+// comment on `main` below), so everything downstream of it reads as dead code to a binary-only build.
+#![allow(dead_code)]
+
+mod crypto;
+mod errors;
+mod handlers;
+mod rpc;
+mod storage;
+
+// Warp filter wiring (routes, TLS, listen address, ...) lives in the server bootstrap and isn't part of
+// this change; `rpc::handle_rpc_call` and `rpc::handle_onion_request` are the two entry points it calls into.
+fn main() {}
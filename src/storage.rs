@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use warp::Rejection;
+
+use super::errors::Error;
+
+#[derive(Debug, Clone)]
+pub struct DatabaseConnectionPool {
+    pub room_id: isize
+}
+
+pub fn pool_by_room_id(room_id: isize) -> Result<DatabaseConnectionPool, Rejection> {
+    return Ok(DatabaseConnectionPool { room_id });
+}
+
+// How long a nonce is remembered for replay-rejection purposes; signed requests are expected to land
+// well within this window, so anything older is safe to forget.
+const NONCE_EXPIRY: Duration = Duration::from_secs(5 * 60);
+
+fn seen_nonces() -> &'static Mutex<HashMap<String, SystemTime>> {
+    static SEEN_NONCES: OnceLock<Mutex<HashMap<String, SystemTime>>> = OnceLock::new();
+    return SEEN_NONCES.get_or_init(|| Mutex::new(HashMap::new()));
+}
+
+/// Records `nonce` as used for `pubkey`, rejecting it if it was already seen within the expiry window.
+/// Callers must verify the accompanying signature before calling this, since persisting a nonce is only
+/// meaningful once the request has been authenticated.
+pub fn check_and_store_nonce(pubkey: &str, nonce: &str, _pool: &DatabaseConnectionPool) -> Result<(), Rejection> {
+    let key = format!("{}:{}", pubkey, nonce);
+    let now = SystemTime::now();
+    let mut nonces = seen_nonces().lock().unwrap();
+    nonces.retain(|_, seen_at| now.duration_since(*seen_at).unwrap_or(Duration::ZERO) < NONCE_EXPIRY);
+    if nonces.contains_key(&key) {
+        return Err(warp::reject::custom(Error::Unauthorized));
+    }
+    nonces.insert(key, now);
+    return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_replayed_nonce_is_rejected_on_second_use() {
+        let pool = DatabaseConnectionPool { room_id: 1 };
+        let pubkey = "test-pubkey-replay";
+        assert!(check_and_store_nonce(pubkey, "nonce-replay", &pool).is_ok());
+        assert!(check_and_store_nonce(pubkey, "nonce-replay", &pool).is_err());
+        // A different nonce for the same pubkey is unaffected by the one already consumed
+        assert!(check_and_store_nonce(pubkey, "nonce-replay-2", &pool).is_ok());
+    }
+}